@@ -62,14 +62,45 @@ pub enum FormData {
         /// [RFC 7578 - Section 4.2](https://datatracker.ietf.org/doc/html/rfc7578#section-4.2)
         data: BytesWrapper,
     },
+    DiskFile {
+        /// The name of the form field. This field MUST be present.
+        /// [RFC 7578 - Section 4.2](https://datatracker.ietf.org/doc/html/rfc7578#section-4.2)
+        name: String,
+
+        /// The filename of the file being uploaded.
+        /// [RFC 7578 - Section 4.2](https://datatracker.ietf.org/doc/html/rfc7578#section-4.2)
+        filename: String,
+
+        /// Each part MAY have a Content-Type header field, which defaults to "text/plain".
+        /// [RFC 7578 - Section 4.4](https://datatracker.ietf.org/doc/html/rfc7578#section-4.4)
+        content_type: String,
+
+        /// The charset to use when decoding the file part.
+        /// [RFC 7578 - Section 4.2](https://datatracker.ietf.org/doc/html/rfc7578#section-4.2)
+        charset: String,
+
+        /// The path to the temporary file the part's data was spilled to once it
+        /// exceeded the parser's `memfile_limit`.
+        path: String,
+
+        /// The total size, in bytes, of the data written to `path`.
+        size: usize,
+    },
+    Nested {
+        /// The name of the form field whose body is itself a nested
+        /// `multipart/*` stream (e.g. several files sent under one field via
+        /// `multipart/mixed`). Its sub-parts are surfaced as top-level
+        /// `FormData::File`/`FormData::DiskFile` parts under this same name (a
+        /// sub-part without a `filename` is surfaced as a `FormData::Field`).
+        name: String,
+    },
 }
 
-impl FormData {
-    pub fn append_data(&mut self, data: Vec<u8>) {
-        match self {
-            FormData::Field { data: field_data, .. } => field_data.0.extend(data),
-            FormData::File { data: file_data, .. } => file_data.0.extend(data),
-        }
+/// Parse the `Content-Type` header out of `headers`, defaulting to `text/plain`.
+fn parse_content_type(headers: &HashMap<String, String>) -> PyResult<(String, HashMap<String, String>)> {
+    match headers.get("content-type") {
+        Some(value) => headers::parse_options_header(value.to_string()).map_err(PyValueError::new_err),
+        None => Ok(("text/plain".to_string(), HashMap::new())),
     }
 }
 
@@ -77,14 +108,7 @@ impl TryFrom<HashMap<String, String>> for FormData {
     type Error = PyErr;
 
     fn try_from(headers: HashMap<String, String>) -> PyResult<Self> {
-        let (content_type, params) = match headers.get("content-type") {
-            Some(value) => match headers::parse_options_header(value.to_string()) {
-                Ok((content_type, params)) => (content_type, params),
-                Err(e) => return Err(PyValueError::new_err(e)),
-            },
-            None => ("text/plain".to_string(), HashMap::new()),
-        };
-
+        let (content_type, params) = parse_content_type(&headers)?;
         let charset = params.get("charset").unwrap_or(&"utf-8".to_string()).to_string();
 
         let (content_disposition, params) = match headers.get("content-disposition") {
@@ -124,3 +148,38 @@ impl TryFrom<HashMap<String, String>> for FormData {
         }
     }
 }
+
+impl FormData {
+    /// Build a `FormData` part for a sub-part of a nested `multipart/mixed` body (see
+    /// [`FormData::Nested`]). Per [RFC 2388 Section 5.2](https://datatracker.ietf.org/doc/html/rfc2388#section-5.2),
+    /// these sub-parts use `Content-Disposition: attachment; filename="..."` with no `name`
+    /// parameter of their own — the name is inherited from the outer field instead, so unlike
+    /// `FormData::try_from` this neither requires a `form-data` disposition nor a `name`.
+    pub(crate) fn try_from_nested(headers: HashMap<String, String>, outer_name: &str) -> PyResult<Self> {
+        let (content_type, params) = parse_content_type(&headers)?;
+        let charset = params.get("charset").unwrap_or(&"utf-8".to_string()).to_string();
+
+        let params = match headers.get("content-disposition") {
+            Some(value) => headers::parse_options_header(value.to_string())
+                .map(|(_, params)| params)
+                .map_err(PyValueError::new_err)?,
+            None => HashMap::new(),
+        };
+
+        match params.get("filename") {
+            Some(filename) => Ok(FormData::File {
+                name: outer_name.to_string(),
+                filename: filename.clone(),
+                content_type,
+                charset,
+                data: BytesWrapper(Vec::new()),
+            }),
+            None => Ok(FormData::Field {
+                name: outer_name.to_string(),
+                content_type,
+                charset,
+                data: BytesWrapper(Vec::new()),
+            }),
+        }
+    }
+}
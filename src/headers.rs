@@ -1,20 +1,121 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Parse `Content-Type` like headers.
 ///
 /// Function name inspired by `werkzeug.parse_options_header` in Python.
 /// See.: https://tedboy.github.io/flask/generated/werkzeug.parse_options_header.html
+///
+/// Parameter values may be quoted (with `\"` escapes, and semicolons inside the
+/// quotes are not treated as separators) and may use the RFC 5987 extended
+/// form (e.g. `filename*=UTF-8''%e2%82%ac.txt`). An extended value is decoded
+/// and exposed under its base key (`filename*` -> `filename`), taking
+/// precedence over a plain duplicate of the same key.
+/// See.: https://datatracker.ietf.org/doc/html/rfc5987
 pub fn parse_options_header(value: String) -> Result<(String, HashMap<String, String>), String> {
-    let mut parts = value.split(';');
+    let mut tokens = split_header_tokens(&value).into_iter();
 
-    let name = parts.next().ok_or("Missing header name")?.trim();
+    let name = tokens.next().ok_or("Missing header name")?.trim().to_string();
     let mut parameters = HashMap::new();
+    let mut extended_keys = HashSet::new();
 
-    for part in parts {
-        let mut parameter_parts = part.splitn(2, '=');
+    for token in tokens {
+        let mut parameter_parts = token.splitn(2, '=');
         let key = parameter_parts.next().ok_or("Missing parameter key")?.trim();
-        let value = parameter_parts.next().ok_or("Missing parameter value")?.trim();
-        parameters.insert(key.to_string(), value.to_string());
+        let raw_value = parameter_parts.next().ok_or("Missing parameter value")?.trim();
+
+        match key.strip_suffix('*') {
+            Some(base_key) => {
+                parameters.insert(base_key.to_string(), decode_extended_value(raw_value)?);
+                extended_keys.insert(base_key.to_string());
+            }
+            None if !extended_keys.contains(key) => {
+                parameters.insert(key.to_string(), unquote(raw_value));
+            }
+            None => {}
+        }
+    }
+
+    Ok((name, parameters))
+}
+
+/// Split a header value on `;`, treating a `"`-delimited region as opaque (honoring
+/// `\"` escapes) so that a semicolon inside a quoted parameter value is not mistaken
+/// for a separator.
+fn split_header_tokens(value: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+
+        match c {
+            '\\' if in_quotes => {
+                current.push(c);
+                escaped = true;
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            ';' if !in_quotes => tokens.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    tokens.push(current);
+
+    tokens
+}
+
+/// Strip surrounding double quotes from `value`, if present, and unescape `\"`/`\\`.
+fn unquote(value: &str) -> String {
+    match value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        true => value[1..value.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\"),
+        false => value.to_string(),
+    }
+}
+
+/// Decode an RFC 5987 `ext-value`: `charset "'" [language] "'" pct-encoded-value`.
+fn decode_extended_value(value: &str) -> Result<String, String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next().ok_or("Missing charset in extended value")?;
+    let _language = parts.next().ok_or("Missing language in extended value")?;
+    let encoded = parts.next().ok_or("Missing value in extended value")?;
+
+    decode_charset(&percent_decode(encoded)?, charset)
+}
+
+/// Decode a percent-encoded (`%XX`) ASCII string into raw bytes.
+fn percent_decode(value: &str) -> Result<Vec<u8>, String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3).ok_or("Invalid percent-encoding")?;
+            let hex = std::str::from_utf8(hex).map_err(|_| "Invalid percent-encoding")?;
+            decoded.push(u8::from_str_radix(hex, 16).map_err(|_| "Invalid percent-encoding")?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    Ok(decoded)
+}
+
+/// Decode `bytes` using one of the charset labels supported for extended values.
+fn decode_charset(bytes: &[u8], charset: &str) -> Result<String, String> {
+    match charset.to_lowercase().as_str() {
+        "utf-8" | "utf8" => String::from_utf8(bytes.to_vec()).map_err(|_| "Invalid UTF-8 in extended value".to_string()),
+        "iso-8859-1" | "latin1" | "latin-1" => Ok(bytes.iter().map(|&b| b as char).collect()),
+        other => Err(format!("Unsupported charset in extended value: {other}")),
     }
-    Ok((name.to_string(), parameters))
 }
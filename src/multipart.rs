@@ -15,21 +15,41 @@
 //! ```
 
 use core::fmt;
-use std::{collections::HashMap, str};
+use std::io::Write;
+use std::collections::HashMap;
 
+use encoding_rs::Encoding;
 use log::debug;
 use pyo3::{
     exceptions::{PyRuntimeError, PyValueError},
     prelude::*,
     types::PyBytes,
 };
+use tempfile::NamedTempFile;
 
 use crate::form_data::FormData;
+use crate::headers;
 
 const CR: u8 = b'\r';
 const LF: u8 = b'\n';
 const CRLF: [u8; 2] = [CR, LF];
 
+/// Maximum number of bytes kept in memory for a single non-file field. Fields are
+/// never spilled to disk, unlike `File` parts, which honor `memfile_limit`.
+const FIELD_MEMORY_LIMIT: usize = 1024 * 1024;
+
+/// The open temp file a `File` part is being spilled to once its buffered size
+/// exceeds `memfile_limit`.
+struct SpillState {
+    file: std::fs::File,
+    path: std::path::PathBuf,
+    size: usize,
+}
+
+fn spill_err(error: impl fmt::Display) -> PyErr {
+    PyValueError::new_err(format!("Failed to spill file part to disk: {error}"))
+}
+
 #[pyclass(eq, eq_int)]
 #[derive(Clone, PartialEq, Debug)]
 pub enum MultipartState {
@@ -66,18 +86,16 @@ pub enum MultipartPart {
 }
 
 impl MultipartPart {
-    fn build_header(data: &[u8]) -> PyResult<Self> {
+    fn build_header(data: &[u8], encoding: &'static Encoding) -> PyResult<Self> {
         let parts = match data.iter().position(|&c| c == b':') {
             Some(index) => index,
             None => return Err(PyValueError::new_err("Malformed header")),
         };
 
-        let key = &data[..parts];
-        let value = &data[parts + 1..];
-
-        // TODO: The encoding should be determined by the HTTP Content-Type header.
-        let key = str::from_utf8(key).map_err(|_| PyValueError::new_err("Invalid key"))?.trim();
-        let value = str::from_utf8(value).map_err(|_| PyValueError::new_err("Invalid value"))?.trim();
+        let key = decode_strict(&data[..parts], encoding, "key")?;
+        let value = decode_strict(&data[parts + 1..], encoding, "value")?;
+        let key = key.trim();
+        let value = value.trim();
 
         Ok(MultipartPart::Header {
             name: key.to_lowercase(),
@@ -101,9 +119,35 @@ pub struct MultipartParser {
     _boundary: Vec<u8>,
     max_size: Option<usize>,
 
-    // TODO: How can I use `str` instead of `String` here?
-    /// The charset to use when decoding headers.
-    _header_charset: String,
+    /// The in-memory size, in bytes, a `File` part's data may reach before it is
+    /// spilled to a temp file on disk. `None` keeps every `File` part in memory.
+    memfile_limit: Option<usize>,
+
+    /// The maximum number of parts the stream may contain. `None` means unlimited.
+    max_parts: Option<usize>,
+
+    /// The maximum number of headers a single part may carry. `None` means unlimited.
+    max_headers_per_part: Option<usize>,
+
+    /// The maximum cumulative size, in bytes, of a single part's data. `None` means unlimited.
+    max_part_size: Option<usize>,
+
+    /// The maximum depth of nested `multipart/mixed` parts (see [`FormData::Nested`]). A
+    /// top-level parser is at depth 0; each child spawned by `start_nested_part` is one
+    /// deeper than its parent. `None` means unlimited, which allows unbounded recursion.
+    max_nesting_depth: Option<usize>,
+
+    /// This parser's own nesting depth (0 for a top-level parser), checked against
+    /// `max_nesting_depth` before spawning a child for a nested `multipart/*` part.
+    _nesting_depth: usize,
+
+    /// Whether only `CRLF` line breaks are accepted (the spec-compliant default). When `false`,
+    /// a lone `CR` or `LF` is also accepted wherever a line break is expected, for compatibility
+    /// with older/broken clients.
+    strict_newlines: bool,
+
+    /// The charset to use when decoding header names and values.
+    _header_encoding: &'static Encoding,
 
     _state: MultipartState,
     _buffer: Vec<u8>,
@@ -111,8 +155,10 @@ pub struct MultipartParser {
     /// The boundary with a leading `--`.
     _dash_boundary: Vec<u8>,
 
-    /// The combination of CRLF + `--` + boundary.
-    _delimiter: Vec<u8>,
+    /// Set on a child parser created by `start_nested_part` to the outer field's name, so
+    /// `handle_header` can build its `FormData` parts via `FormData::try_from_nested` instead
+    /// of requiring the `form-data`/`name` form `FormData::try_from` expects.
+    _inherited_name: Option<String>,
 
     _offset: usize,
     _events: Vec<MultipartPart>,
@@ -121,48 +167,97 @@ pub struct MultipartParser {
     /// The headers of the current part.
     _current_headers: HashMap<String, String>,
 
+    /// The number of headers seen so far for the current part.
+    _current_header_count: usize,
+
     /// The current part being parsed.
     _current_part: Option<FormData>,
 
     /// The parsed parts.
     _parts: Vec<FormData>,
+
+    /// The number of parts fully parsed so far.
+    _parts_count: usize,
+
+    /// The temp file the current `File` part is being spilled to, if its data has
+    /// exceeded `memfile_limit`.
+    _spill: Option<SpillState>,
+
+    /// Paths of temp files created by spilling a `File` part to disk that have not yet been
+    /// surfaced to Python as a completed `DiskFile` part. Cleaned up if `parse()` errors out,
+    /// so an aborted parse (e.g. a later part tripping `max_parts`) doesn't leak them.
+    _spilled_paths: Vec<std::path::PathBuf>,
+
+    /// The child parser handling the current part's body, if it is itself a nested
+    /// `multipart/*` stream (see [`FormData::Nested`]), along with the outer field
+    /// name its sub-parts should be surfaced under.
+    _nested: Option<(Box<MultipartParser>, String)>,
 }
 
 #[pymethods]
 impl MultipartParser {
-    // TODO: Can `header_charset` be only `&str`?
     #[new]
-    #[pyo3(signature = (boundary, max_size = None, header_charset = "utf8"))]
-    fn new(boundary: Vec<u8>, max_size: Option<usize>, header_charset: Option<&str>) -> PyResult<Self> {
+    #[pyo3(signature = (
+        boundary,
+        max_size = None,
+        header_charset = "utf8",
+        memfile_limit = None,
+        max_parts = None,
+        max_headers_per_part = Some(32),
+        max_part_size = None,
+        max_nesting_depth = Some(5),
+        strict_newlines = true
+    ))]
+    fn new(
+        boundary: Vec<u8>,
+        max_size: Option<usize>,
+        header_charset: Option<&str>,
+        memfile_limit: Option<usize>,
+        max_parts: Option<usize>,
+        max_headers_per_part: Option<usize>,
+        max_part_size: Option<usize>,
+        max_nesting_depth: Option<usize>,
+        strict_newlines: bool,
+    ) -> PyResult<Self> {
         // According to https://www.rfc-editor.org/rfc/rfc2046.html#section-5.1.1, the boundary
         // should be between 1 and 70 bytes.
         if boundary.len() < 1 || boundary.len() > 70 {
             return Err(PyValueError::new_err("Boundary length must be between 1 and 70 characters."));
         }
 
-        // TODO: Implement more header charset support.
-        if header_charset != Some("utf8") {
-            return Err(PyRuntimeError::new_err("The only supported charset is 'utf8'."));
-        }
+        let header_charset = header_charset.unwrap_or("utf8");
+        let _header_encoding = Encoding::for_label(header_charset.as_bytes())
+            .ok_or_else(|| PyValueError::new_err(format!("Unsupported header charset: {header_charset}")))?;
 
         let _dash_boundary = [b"--".as_slice(), &boundary].concat();
-        let _delimiter = [b"\r\n".as_slice(), &_dash_boundary].concat();
 
         Ok(MultipartParser {
             _boundary: boundary,
             max_size: max_size,
-            _header_charset: header_charset.unwrap_or("utf8").to_string(),
+            memfile_limit,
+            max_parts,
+            max_headers_per_part,
+            max_part_size,
+            max_nesting_depth,
+            _nesting_depth: 0,
+            strict_newlines,
+            _header_encoding,
             _state: MultipartState::Preamble,
             _buffer: Vec::new(),
             _dash_boundary,
-            _delimiter,
+            _inherited_name: None,
             _offset: 0,
             _events: Vec::new(),
             _need_data: false,
 
             _current_headers: HashMap::new(),
+            _current_header_count: 0,
             _current_part: None,
             _parts: Vec::new(),
+            _parts_count: 0,
+            _spill: None,
+            _spilled_paths: Vec::new(),
+            _nested: None,
         })
     }
 
@@ -183,6 +278,31 @@ impl MultipartParser {
         self._buffer.extend(data);
         self._need_data = false;
 
+        let result = self.run_state_machine();
+        if result.is_err() {
+            self.cleanup_spilled_files();
+        }
+        result
+    }
+
+    fn next_part(&mut self) -> PyResult<Option<FormData>> {
+        match self._parts.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(self._parts.remove(0))),
+        }
+    }
+
+    fn next_event(&mut self) -> PyResult<Option<MultipartPart>> {
+        match self._events.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(self._events.remove(0))),
+        }
+    }
+}
+
+impl MultipartParser {
+    /// Drive the state machine until it needs more data or reaches `End`.
+    fn run_state_machine(&mut self) -> PyResult<()> {
         loop {
             self._state = match self._state {
                 MultipartState::Preamble => self.handle_preamble(),
@@ -200,52 +320,59 @@ impl MultipartParser {
         Ok(())
     }
 
-    fn next_part(&mut self) -> PyResult<Option<FormData>> {
-        match self._parts.is_empty() {
-            true => Ok(None),
-            false => Ok(Some(self._parts.remove(0))),
+    /// Best-effort removal of temp files spilled to disk whose `DiskFile` part never made it
+    /// out as a completed part (e.g. because a later part tripped a limit and aborted the
+    /// parse), so they aren't left behind with no path ever handed back to Python.
+    fn cleanup_spilled_files(&mut self) {
+        for path in self._spilled_paths.drain(..) {
+            let _ = std::fs::remove_file(path);
         }
     }
 
-    fn next_event(&mut self) -> PyResult<Option<MultipartPart>> {
-        match self._events.is_empty() {
-            true => Ok(None),
-            false => Ok(Some(self._events.remove(0))),
-        }
-    }
-}
-
-impl MultipartParser {
     fn handle_preamble(&mut self) -> PyResult<MultipartState> {
         let delimiter = self._dash_boundary.clone();
         let delimiter_len = delimiter.len();
         let buffer = self._buffer[self._offset..].to_vec();
 
         if let Some(index) = buffer.windows(delimiter_len).position(|window| window == delimiter) {
-            if let Some(after_delimiter) = buffer.get(index + delimiter_len..) {
-                let tail = after_delimiter.get(..2).unwrap_or_default();
+            let after_delimiter = &buffer[index + delimiter_len..];
 
-                // First delimiter found -> End of preamble
-                if tail == CRLF {
-                    self._offset += index + delimiter_len + 2;
-                    return Ok(MultipartState::Header);
-                }
-
-                // First delimiter is terminator -> Empty multipart stream
-                if tail == b"--" {
-                    return Ok(MultipartState::End);
-                }
+            // First delimiter found -> End of preamble
+            if after_delimiter.starts_with(&CRLF) {
+                self._offset += index + delimiter_len + 2;
+                return Ok(MultipartState::Header);
+            }
 
-                // Bad newline after valid delimiter -> Broken client
-                if tail == b"\n" {
-                    return Err(PyValueError::new_err("Invalid line break after delimiter"));
-                }
+            // First delimiter is terminator -> Empty multipart stream
+            if after_delimiter.starts_with(b"--") {
+                return Ok(MultipartState::End);
+            }
 
-                // CR found after delimiter, but next byte is not LF -> Move offset
-                if tail.len() > 1 && tail[0] == CR {
+            match after_delimiter.first() {
+                Some(&CR) => match after_delimiter.get(1) {
+                    // CR found after delimiter, but next byte is not LF (already ruled out
+                    // above). In strict mode this is not a genuine delimiter -> move past it
+                    // and keep scanning. In lax mode a lone CR is itself a valid terminator.
+                    Some(_) if self.strict_newlines => {
+                        self._offset += index + delimiter_len + 1;
+                        return Ok(MultipartState::Preamble);
+                    }
+                    Some(_) => {
+                        self._offset += index + delimiter_len + 1;
+                        return Ok(MultipartState::Header);
+                    }
+                    // Not enough data yet to know whether this CR is followed by LF.
+                    None => {}
+                },
+                // Bad newline after valid delimiter -> Broken client, unless lax mode.
+                Some(&LF) => {
+                    if self.strict_newlines {
+                        return Err(PyValueError::new_err("Invalid line break after delimiter"));
+                    }
                     self._offset += index + delimiter_len + 1;
-                    return Ok(MultipartState::Preamble);
+                    return Ok(MultipartState::Header);
                 }
+                _ => {}
             }
         }
 
@@ -260,22 +387,39 @@ impl MultipartParser {
 
         debug!("Buffer: {:?}", bytes_to_str(buffer.clone()));
 
-        // We are looking for a CRLF sequence to separate headers from body.
-        match buffer.windows(2).position(|window| window == CRLF) {
-            Some(index) => {
+        // We are looking for a line terminator to separate headers from body (CRLF in strict
+        // mode; CRLF, a lone CR, or a lone LF in lax mode).
+        match find_line_terminator(&buffer, self.strict_newlines) {
+            Some((index, terminator_len)) => {
                 debug!("{:?}: header found at index: {}.", self._state, index);
                 // Empty line found, move to body
                 if index == 0 {
-                    self._offset = self._offset + 2;
+                    self._offset = self._offset + terminator_len;
 
-                    self._current_part = match FormData::try_from(self._current_headers.clone()) {
-                        Ok(part) => Some(part),
-                        Err(e) => return Err(e),
-                    };
+                    let headers = self._current_headers.clone();
+                    self._current_part = Some(match &self._inherited_name {
+                        Some(outer_name) => FormData::try_from_nested(headers.clone(), outer_name)?,
+                        None => FormData::try_from(headers.clone())?,
+                    });
+                    if let Some(nested) = self.start_nested_part(&headers)? {
+                        self._current_part = Some(nested);
+                    }
+                    self._current_headers.clear();
+                    self._current_header_count = 0;
                     return Ok(MultipartState::Body);
                 } else {
-                    self._offset = self._offset + index + 2;
-                    match MultipartPart::build_header(&buffer[..index]) {
+                    self._offset = self._offset + index + terminator_len;
+
+                    self._current_header_count += 1;
+                    if let Some(max_headers) = self.max_headers_per_part {
+                        if self._current_header_count > max_headers {
+                            return Err(PyValueError::new_err(format!(
+                                "Part exceeds maximum number of headers ({max_headers})."
+                            )));
+                        }
+                    }
+
+                    match MultipartPart::build_header(&buffer[..index], self._header_encoding) {
                         Ok(MultipartPart::Header { name, value }) => {
                             self._events.push(MultipartPart::Header {
                                 name: name.clone(),
@@ -291,6 +435,12 @@ impl MultipartParser {
                     return Ok(MultipartState::Header);
                 }
             }
+            None if !self.strict_newlines => {
+                // No terminator resolved yet (no CR or LF found, or only a trailing CR that may
+                // still turn out to be CRLF); wait for more data.
+                self._need_data = true;
+                Ok(MultipartState::Header)
+            }
             None => match buffer.windows(1).position(|window| window == &[LF]) {
                 Some(_) => {
                     return Err(PyValueError::new_err("Invalid line break in header"));
@@ -306,80 +456,584 @@ impl MultipartParser {
 
     fn handle_body(&mut self) -> PyResult<MultipartState> {
         let buffer = self._buffer[self._offset..].to_vec();
-        let delimiter = self._delimiter.clone();
-        let delimiter_len = delimiter.len();
+        let dash_boundary = self._dash_boundary.clone();
+        let dash_len = dash_boundary.len();
 
         debug!("Buffer: {:?}", bytes_to_str(buffer.clone()));
 
-        match buffer.windows(delimiter.len()).position(|window| window == delimiter) {
-            Some(index) => {
-                debug!("{:?}: delimiter found at index: {}.", self._state, index);
-                match buffer.get(index + delimiter_len..index + delimiter_len + 2) {
-                    Some(tail) => match tail {
-                        [CR, LF] => {
-                            debug!("{:?}: delimiter is CRLF.", self._state);
-                            self._events.push(MultipartPart::Body {
-                                data: BytesWrapper(buffer[..index].to_vec()),
-                                complete: true,
-                            });
-                            self.insert_data(buffer[..index].to_vec(), true)?;
-                            self._offset += index + delimiter_len + 2;
-                            return Ok(MultipartState::Header);
-                        }
-                        // Delimiter was terminator, end of multipart stream.
-                        [b'-', b'-'] => {
-                            self.insert_data(buffer[..index].to_vec(), true)?;
-                            self._events.push(MultipartPart::Body {
-                                data: BytesWrapper(buffer[..index].to_vec()),
-                                complete: true,
-                            });
-                            self._offset += index + delimiter_len + 2;
-                            return Ok(MultipartState::End);
-                        }
-                        _ => {
-                            self._need_data = true;
-                            return Ok(MultipartState::Body);
-                        }
-                    },
-                    None => {
-                        self._need_data = true;
-                        return Ok(MultipartState::Body);
-                    }
-                };
-            }
-            None => {
-                // Delimiter not found, wait for more data.
-                debug!("{:?}: delimiter not found.", self._state);
-                if buffer.len() > delimiter_len + 3 {
-                    self.insert_data(buffer[..buffer.len() - (delimiter_len + 3)].to_vec(), false)?;
+        // The margin of trailing bytes that might still be part of an as-yet-incomplete
+        // delimiter (leading line break + dash-boundary + trailing line break/terminator).
+        let margin = dash_len + 5;
+
+        // Scan for occurrences of the raw dash-boundary bytes, same as the pre-refactor single
+        // `CRLF + boundary` token search did in one pass: a match not preceded by a valid line
+        // break is just the boundary text showing up inside binary content, so keep scanning
+        // from just past it rather than giving up and waiting for more data.
+        let mut search_start = 0;
+        while let Some(relative_index) = buffer[search_start..].windows(dash_len).position(|window| window == dash_boundary) {
+            let dash_index = search_start + relative_index;
+
+            let lead = match self.leading_break_len(&buffer, dash_index) {
+                Some(lead) => lead,
+                None => {
+                    search_start = dash_index + 1;
+                    continue;
+                }
+            };
+
+            let content_end = dash_index - lead;
+            let after_dash = &buffer[dash_index + dash_len..];
+
+            match classify_body_suffix(after_dash, self.strict_newlines) {
+                BodySuffix::Header(suffix_len) => {
+                    debug!("{:?}: delimiter found at index: {}.", self._state, dash_index);
+                    self.insert_data(buffer[..content_end].to_vec(), true)?;
                     self._events.push(MultipartPart::Body {
-                        data: BytesWrapper(buffer[..buffer.len() - (delimiter_len + 3)].to_vec()),
-                        complete: false,
+                        data: BytesWrapper(buffer[..content_end].to_vec()),
+                        complete: true,
                     });
-                    self._offset = self._buffer.len() - (delimiter_len + 3);
+                    self._offset += dash_index + dash_len + suffix_len;
+                    return Ok(MultipartState::Header);
+                }
+                // Delimiter was terminator, end of multipart stream.
+                BodySuffix::End => {
+                    self.insert_data(buffer[..content_end].to_vec(), true)?;
+                    self._events.push(MultipartPart::Body {
+                        data: BytesWrapper(buffer[..content_end].to_vec()),
+                        complete: true,
+                    });
+                    self._offset += dash_index + dash_len + 2;
+                    return Ok(MultipartState::End);
+                }
+                BodySuffix::NeedMoreData => {
+                    self._need_data = true;
+                    return Ok(MultipartState::Body);
                 }
-                self._need_data = true;
-                Ok(MultipartState::Body)
             }
         }
+
+        // Delimiter not found, wait for more data.
+        debug!("{:?}: delimiter not found.", self._state);
+        if buffer.len() > margin {
+            self.insert_data(buffer[..buffer.len() - margin].to_vec(), false)?;
+            self._events.push(MultipartPart::Body {
+                data: BytesWrapper(buffer[..buffer.len() - margin].to_vec()),
+                complete: false,
+            });
+            self._offset = self._buffer.len() - margin;
+        }
+        self._need_data = true;
+        Ok(MultipartState::Body)
+    }
+
+    /// Determine how many bytes before `dash_index` form the line break that introduces a
+    /// dash-boundary match found in the body (`CRLF` in strict mode; `CRLF`, a lone `CR`, or a
+    /// lone `LF` in lax mode). Returns `None` if no valid line break precedes the match, meaning
+    /// it is not a genuine delimiter.
+    fn leading_break_len(&self, buffer: &[u8], dash_index: usize) -> Option<usize> {
+        if dash_index >= 2 && buffer[dash_index - 2..dash_index] == CRLF {
+            return Some(2);
+        }
+        if !self.strict_newlines && dash_index >= 1 && matches!(buffer[dash_index - 1], CR | LF) {
+            return Some(1);
+        }
+        None
     }
 
     fn insert_data(&mut self, data: Vec<u8>, complete: bool) -> PyResult<()> {
-        match self._current_part.take() {
-            Some(mut part) => {
-                part.append_data(data);
+        let part = match self._current_part.take() {
+            Some(part) => part,
+            None => return Err(PyValueError::new_err("Missing current part")),
+        };
+
+        let current_size = match &part {
+            FormData::Field { data, .. } => data.0.len(),
+            FormData::File { data, .. } => self._spill.as_ref().map_or(data.0.len(), |spill| spill.size),
+            FormData::DiskFile { .. } | FormData::Nested { .. } => 0,
+        };
+        // A nested multipart stream's own parts are bounded by the limits the child
+        // parser was constructed with, not by `max_part_size` on the raw sub-stream bytes.
+        if let (Some(max_part_size), false) = (self.max_part_size, matches!(&part, FormData::Nested { .. })) {
+            if current_size + data.len() > max_part_size {
+                return Err(PyValueError::new_err(format!("Part exceeds maximum size of {max_part_size} bytes.")));
+            }
+        }
+
+        let finished = match part {
+            FormData::Field { name, content_type, charset, data: mut field_data } => {
+                if field_data.0.len() + data.len() > FIELD_MEMORY_LIMIT {
+                    return Err(PyValueError::new_err("Field data exceeds maximum in-memory size"));
+                }
+                field_data.0.extend(data);
 
                 if complete {
-                    self._parts.push(part);
-                    self._current_part = None;
+                    field_data.0 = decode_field_data(field_data.0, &charset);
+                    // `data` is now always UTF-8 bytes regardless of the wire charset, so the
+                    // label handed back must say so too, or a caller that decodes `data` using
+                    // `charset` (the documented contract) would double-decode it.
+                    Some(FormData::Field { name, content_type, charset: "utf-8".to_string(), data: field_data })
+                } else {
+                    self._current_part = Some(FormData::Field { name, content_type, charset, data: field_data });
+                    None
                 }
             }
-            None => return Err(PyValueError::new_err("Missing current part")),
+            FormData::File { name, filename, content_type, charset, data: mut file_data } => {
+                match self._spill.as_mut() {
+                    Some(spill) => {
+                        spill.file.write_all(&data).map_err(spill_err)?;
+                        spill.size += data.len();
+                    }
+                    None => match self.memfile_limit {
+                        Some(limit) if file_data.0.len() + data.len() > limit => {
+                            let mut temp = NamedTempFile::new().map_err(spill_err)?;
+                            temp.write_all(&file_data.0).map_err(spill_err)?;
+                            temp.write_all(&data).map_err(spill_err)?;
+                            let size = file_data.0.len() + data.len();
+                            file_data.0.clear();
+
+                            let (file, temp_path) = temp.into_parts();
+                            let path = temp_path.keep().map_err(spill_err)?;
+                            self._spilled_paths.push(path.clone());
+                            self._spill = Some(SpillState { file, path, size });
+                        }
+                        _ => file_data.0.extend(data),
+                    },
+                }
+
+                let part = FormData::File { name, filename, content_type, charset, data: file_data };
+
+                if !complete {
+                    self._current_part = Some(part);
+                    None
+                } else if let Some(spill) = self._spill.take() {
+                    let (name, filename, content_type, charset) = match part {
+                        FormData::File { name, filename, content_type, charset, .. } => (name, filename, content_type, charset),
+                        _ => unreachable!(),
+                    };
+                    Some(FormData::DiskFile {
+                        name,
+                        filename,
+                        content_type,
+                        charset,
+                        path: spill.path.to_string_lossy().into_owned(),
+                        size: spill.size,
+                    })
+                } else {
+                    Some(part)
+                }
+            }
+            FormData::DiskFile { .. } => return Err(PyValueError::new_err("Current part cannot already be a disk file")),
+            FormData::Nested { name } => {
+                let (mut child, child_name) = self._nested.take().expect("nested state missing for a Nested part");
+                let parse_result = child.parse(data);
+
+                // Drain whatever the child already produced regardless of whether `parse`
+                // above succeeded, so a completed (and possibly already-spilled-to-disk)
+                // child part is never simply dropped along with `child`: either it's pushed
+                // through as one of our own parts, or its temp file is reclaimed right here.
+                let mut push_result: PyResult<()> = Ok(());
+                while let Some(part) = child.next_part()? {
+                    let part = rename_part(part, &name);
+                    if push_result.is_ok() {
+                        let disk_path = match &part {
+                            FormData::DiskFile { path, .. } => Some(path.clone()),
+                            _ => None,
+                        };
+                        push_result = self.push_part(part);
+                        if push_result.is_err() {
+                            if let Some(path) = disk_path {
+                                let _ = std::fs::remove_file(path);
+                            }
+                        }
+                    } else {
+                        delete_disk_file_part(&part);
+                    }
+                }
+                while let Some(event) = child.next_event()? {
+                    self._events.push(event);
+                }
+
+                parse_result?;
+                push_result?;
+
+                if complete {
+                    None
+                } else {
+                    self._nested = Some((child, child_name));
+                    self._current_part = Some(FormData::Nested { name });
+                    None
+                }
+            }
+        };
+
+        if let Some(part) = finished {
+            self.push_part(part)?;
+        }
+
+        Ok(())
+    }
+
+    /// Push a finished part, enforcing `max_parts` against the running count.
+    fn push_part(&mut self, part: FormData) -> PyResult<()> {
+        self._parts_count += 1;
+        if let Some(max_parts) = self.max_parts {
+            if self._parts_count > max_parts {
+                return Err(PyValueError::new_err(format!("Exceeds maximum number of parts ({max_parts}).")));
+            }
+        }
+        // Its path is now reachable via `next_part`, so it no longer needs cleanup on error.
+        if let FormData::DiskFile { path, .. } = &part {
+            self._spilled_paths.retain(|spilled| spilled.to_str() != Some(path.as_str()));
         }
+        self._parts.push(part);
         Ok(())
     }
+
+    /// If `headers` declare a `multipart/*` `Content-Type` with a `boundary` parameter,
+    /// switch the current part to a [`FormData::Nested`] container backed by a child
+    /// [`MultipartParser`] for that boundary, sharing this parser's size/part limits.
+    fn start_nested_part(&mut self, headers: &HashMap<String, String>) -> PyResult<Option<FormData>> {
+        let content_type_header = match headers.get("content-type") {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let (content_type, params) =
+            headers::parse_options_header(content_type_header.to_string()).map_err(PyValueError::new_err)?;
+
+        if !content_type.starts_with("multipart/") {
+            return Ok(None);
+        }
+
+        let boundary = match params.get("boundary") {
+            Some(boundary) => boundary.clone(),
+            None => return Ok(None),
+        };
+
+        let name = match self._current_part.as_ref() {
+            Some(FormData::Field { name, .. }) | Some(FormData::File { name, .. }) => name.clone(),
+            _ => return Ok(None),
+        };
+
+        if let Some(max_nesting_depth) = self.max_nesting_depth {
+            if self._nesting_depth >= max_nesting_depth {
+                return Err(PyValueError::new_err(format!(
+                    "Exceeds maximum nesting depth ({max_nesting_depth})."
+                )));
+            }
+        }
+
+        let mut child = MultipartParser::new(
+            boundary.into_bytes(),
+            self.max_size,
+            Some(self._header_encoding.name()),
+            self.memfile_limit,
+            self.max_parts,
+            self.max_headers_per_part,
+            self.max_part_size,
+            self.max_nesting_depth,
+            self.strict_newlines,
+        )?;
+        child._inherited_name = Some(name.clone());
+        child._nesting_depth = self._nesting_depth + 1;
+
+        self._nested = Some((Box::new(child), name.clone()));
+
+        Ok(Some(FormData::Nested { name }))
+    }
+}
+
+/// Re-associate a part surfaced by a nested `multipart/*` child parser with the
+/// outer field `name` it belongs to.
+fn rename_part(part: FormData, name: &str) -> FormData {
+    match part {
+        FormData::File { filename, content_type, charset, data, .. } => FormData::File {
+            name: name.to_string(),
+            filename,
+            content_type,
+            charset,
+            data,
+        },
+        FormData::DiskFile { filename, content_type, charset, path, size, .. } => FormData::DiskFile {
+            name: name.to_string(),
+            filename,
+            content_type,
+            charset,
+            path,
+            size,
+        },
+        other => other,
+    }
+}
+
+/// Delete `part`'s spilled temp file, if it has one, because it will never be surfaced to
+/// Python (e.g. a sibling part already failed to push past `max_parts` while draining a
+/// nested child), so it would otherwise leak.
+fn delete_disk_file_part(part: &FormData) {
+    if let FormData::DiskFile { path, .. } = part {
+        let _ = std::fs::remove_file(path);
+    }
 }
 
+/// Decode `bytes` with `encoding`, erroring if any malformed sequence is encountered.
+fn decode_strict(bytes: &[u8], encoding: &'static Encoding, what: &str) -> PyResult<String> {
+    let (text, _, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        return Err(PyValueError::new_err(format!("Invalid {what}")));
+    }
+    Ok(text.into_owned())
+}
+
+/// Decode a field's data with the charset carried by its own `Content-Type`, re-encoding it
+/// as UTF-8 so Python always receives uniformly encoded text regardless of the wire charset.
+/// Unrecognized labels and malformed sequences fall back to lossy UTF-8 rather than failing
+/// the whole parse over a single field.
+fn decode_field_data(data: Vec<u8>, charset: &str) -> Vec<u8> {
+    match Encoding::for_label(charset.as_bytes()) {
+        Some(encoding) if encoding != encoding_rs::UTF_8 => encoding.decode(&data).0.into_owned().into_bytes(),
+        _ => match String::from_utf8(data) {
+            Ok(text) => text.into_bytes(),
+            Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned().into_bytes(),
+        },
+    }
+}
+
+/// Lossily decode `data` for debug logging; malformed sequences must never abort the parser.
 fn bytes_to_str(data: Vec<u8>) -> String {
-    String::from_utf8(data).unwrap()
+    String::from_utf8_lossy(&data).into_owned()
+}
+
+/// Locate the next line terminator in `buffer`: strict mode only recognizes `CRLF`; lax mode
+/// also accepts a lone `CR` or `LF`. Returns `(index, terminator_len)`, or `None` if no
+/// terminator can yet be resolved from the bytes available so far.
+fn find_line_terminator(buffer: &[u8], strict: bool) -> Option<(usize, usize)> {
+    if strict {
+        return buffer.windows(2).position(|window| window == CRLF).map(|index| (index, 2));
+    }
+
+    for (index, &byte) in buffer.iter().enumerate() {
+        match byte {
+            CR => {
+                return match buffer.get(index + 1) {
+                    Some(&LF) => Some((index, 2)),
+                    Some(_) => Some((index, 1)),
+                    // Could still turn out to be CRLF once more data arrives.
+                    None => None,
+                };
+            }
+            LF => return Some((index, 1)),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// What follows a dash-boundary match found in the body.
+enum BodySuffix {
+    /// End of the whole multipart stream (the `--` terminator).
+    End,
+    /// Start of the next part's headers, via a line break of the given length.
+    Header(usize),
+    /// Not enough bytes yet to classify the suffix.
+    NeedMoreData,
+}
+
+/// Classify what follows a dash-boundary match in the body: `CRLF` (or, in lax mode, a lone
+/// `CR`/`LF`) starts the next part's headers; `--` ends the stream; anything else means we
+/// need more data before deciding.
+fn classify_body_suffix(after: &[u8], strict: bool) -> BodySuffix {
+    if after.starts_with(b"--") {
+        return BodySuffix::End;
+    }
+    if after.starts_with(&CRLF) {
+        return BodySuffix::Header(2);
+    }
+    if !strict {
+        match after.first() {
+            // A lone CR as the last currently-buffered byte is ambiguous: more data could
+            // still turn it into CRLF, so defer instead of committing to a 1-byte terminator.
+            Some(&CR) => {
+                return if after.len() < 2 {
+                    BodySuffix::NeedMoreData
+                } else {
+                    BodySuffix::Header(1)
+                };
+            }
+            Some(&LF) => return BodySuffix::Header(1),
+            _ => {}
+        }
+    }
+    BodySuffix::NeedMoreData
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_parser(
+        boundary: &str,
+        memfile_limit: Option<usize>,
+        max_parts: Option<usize>,
+        max_nesting_depth: Option<usize>,
+        strict_newlines: bool,
+    ) -> MultipartParser {
+        MultipartParser::new(
+            boundary.as_bytes().to_vec(),
+            None,
+            None,
+            memfile_limit,
+            max_parts,
+            Some(32),
+            None,
+            max_nesting_depth,
+            strict_newlines,
+        )
+        .unwrap()
+    }
+
+    fn drain_parts(parser: &mut MultipartParser) -> Vec<FormData> {
+        let mut parts = Vec::new();
+        while let Some(part) = parser.next_part().unwrap() {
+            parts.push(part);
+        }
+        parts
+    }
+
+    #[test]
+    fn lax_mode_accepts_a_lone_lf_body_terminator() {
+        let mut parser = new_parser("B", None, None, Some(5), false);
+        let data = b"--B\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nhello\n--B--\r\n".to_vec();
+        parser.parse(data).unwrap();
+
+        let parts = drain_parts(&mut parser);
+        assert_eq!(parts.len(), 1);
+        match &parts[0] {
+            FormData::Field { name, data, .. } => {
+                assert_eq!(name, "a");
+                assert_eq!(data.0, b"hello");
+            }
+            other => panic!("expected a Field part, got {other:?}"),
+        }
+        assert_eq!(parser.state().unwrap(), MultipartState::End);
+    }
+
+    #[test]
+    fn strict_mode_rejects_a_lone_lf_body_terminator() {
+        let mut parser = new_parser("B", None, None, Some(5), true);
+        let data = b"--B\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nhello\n--B--\r\n".to_vec();
+        parser.parse(data).unwrap();
+
+        // The lone LF isn't a valid delimiter in strict mode, so the real terminator is
+        // never recognized and the parser is left waiting for more data.
+        assert_eq!(parser.state().unwrap(), MultipartState::Body);
+        assert!(drain_parts(&mut parser).is_empty());
+    }
+
+    #[test]
+    fn boundary_lookalike_with_no_leading_break_is_skipped_in_the_same_buffer() {
+        let mut parser = new_parser("BOUND", None, None, Some(5), true);
+        // The body contains the raw boundary bytes with no preceding line break (as binary
+        // content might), followed later in the same buffer by the genuine terminator.
+        let data =
+            b"--BOUND\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nabc--BOUNDxyz\r\n--BOUND--\r\n".to_vec();
+        parser.parse(data).unwrap();
+
+        let parts = drain_parts(&mut parser);
+        assert_eq!(parts.len(), 1);
+        match &parts[0] {
+            FormData::Field { data, .. } => assert_eq!(data.0, b"abc--BOUNDxyz"),
+            other => panic!("expected a Field part, got {other:?}"),
+        }
+        assert_eq!(parser.state().unwrap(), MultipartState::End);
+    }
+
+    #[test]
+    fn nested_multipart_mixed_is_flattened_under_the_outer_field_name() {
+        let mut parser = new_parser("OUTER", None, None, Some(5), true);
+        let data = [
+            "--OUTER\r\n",
+            "Content-Disposition: form-data; name=\"docs\"\r\n",
+            "Content-Type: multipart/mixed; boundary=INNER\r\n",
+            "\r\n",
+            "--INNER\r\n",
+            "Content-Disposition: attachment; filename=\"a.txt\"\r\n",
+            "\r\n",
+            "AAA\r\n",
+            "--INNER\r\n",
+            "Content-Disposition: attachment; filename=\"b.txt\"\r\n",
+            "\r\n",
+            "BBB\r\n",
+            "--INNER--\r\n",
+            "\r\n--OUTER--\r\n",
+        ]
+        .concat()
+        .into_bytes();
+        parser.parse(data).unwrap();
+
+        let parts = drain_parts(&mut parser);
+        assert_eq!(parts.len(), 2);
+        for part in &parts {
+            match part {
+                FormData::File { name, filename, data, .. } => {
+                    assert_eq!(name, "docs");
+                    match filename.as_str() {
+                        "a.txt" => assert_eq!(data.0, b"AAA"),
+                        "b.txt" => assert_eq!(data.0, b"BBB"),
+                        other => panic!("unexpected filename {other}"),
+                    }
+                }
+                other => panic!("expected a File part, got {other:?}"),
+            }
+        }
+        assert_eq!(parser.state().unwrap(), MultipartState::End);
+    }
+
+    #[test]
+    fn nesting_past_max_nesting_depth_is_rejected() {
+        let mut parser = new_parser("OUTER", None, None, Some(1), true);
+        let data = [
+            "--OUTER\r\n",
+            "Content-Disposition: form-data; name=\"a\"\r\n",
+            "Content-Type: multipart/mixed; boundary=M1\r\n",
+            "\r\n",
+            "--M1\r\n",
+            "Content-Disposition: attachment\r\n",
+            "Content-Type: multipart/mixed; boundary=M2\r\n",
+            "\r\n",
+            // Padding so `handle_body`'s trailing margin doesn't hold back the bytes above
+            // (which must all reach the nested child parser in this single `parse()` call).
+            "XXXXXXXXXXXXXXXXXXXX",
+        ]
+        .concat()
+        .into_bytes();
+
+        let result = parser.parse(data);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("nesting depth"));
+    }
+
+    #[test]
+    fn a_spilled_file_is_removed_when_a_later_part_trips_max_parts() {
+        let mut parser = new_parser("B", Some(4), Some(1), Some(5), true);
+
+        let mut first_chunk = Vec::new();
+        first_chunk.extend_from_slice(b"--B\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nhello\r\n");
+        first_chunk.extend_from_slice(b"--B\r\nContent-Disposition: form-data; name=\"file\"; filename=\"f.bin\"\r\n\r\n");
+        // Large enough (> memfile_limit and > handle_body's trailing margin) to force a spill
+        // of part 2's in-progress data to disk before the terminating boundary has arrived.
+        first_chunk.extend(std::iter::repeat(b'a').take(20));
+        parser.parse(first_chunk).unwrap();
+
+        assert_eq!(parser._spilled_paths.len(), 1);
+        let spilled_path = parser._spilled_paths[0].clone();
+        assert!(spilled_path.exists());
+
+        let second_chunk = b"\r\n--B--\r\n".to_vec();
+        let result = parser.parse(second_chunk);
+
+        assert!(result.is_err());
+        assert!(!spilled_path.exists());
+        assert!(parser._spilled_paths.is_empty());
+    }
 }